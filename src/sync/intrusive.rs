@@ -0,0 +1,242 @@
+use std::ptr;
+use std::cell::UnsafeCell;
+use std::sync::atomic::{AtomicPtr, Ordering};
+
+/// Implemented by types that can be linked into an `intrusive::Queue`.
+///
+/// The queue keeps no node storage of its own: the singly-linked list runs
+/// through whichever field of `Self` this trait exposes, so pushing an
+/// already-allocated `T` (a `Box<T>` or `Arc<T>` turned into a raw pointer)
+/// costs nothing beyond a couple of atomic stores.
+pub trait Node {
+    /// The link to the next node in the queue.
+    fn next(&self) -> &AtomicPtr<Self>
+    where
+        Self: Sized;
+
+    /// Reclaims a node pointer that was pushed but never popped, e.g.
+    /// because the queue was dropped non-empty.
+    ///
+    /// The default assumes nodes are pushed as `Box::into_raw`. Implementors
+    /// that push `Arc::into_raw` pointers instead must override this to call
+    /// `Arc::from_raw`, so the refcount drops correctly rather than leaking
+    /// or double-freeing.
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must be a valid pointer produced by the same allocation this
+    /// node was pushed with (`Box::into_raw` for the default impl), and must
+    /// not be used again afterwards.
+    unsafe fn reclaim(ptr: *mut Self)
+    where
+        Self: Sized,
+    {
+        drop(Box::from_raw(ptr));
+    }
+}
+
+/// An intrusive, unbounded multi-producer single-consumer queue.
+///
+/// Unlike `mpsc_list::Queue`, `push` does not box a fresh `Node<T>` per
+/// call: the caller hands over a raw pointer to a `T` it already owns and
+/// the queue threads the link embedded in `T` itself, so enqueueing is a
+/// pure pointer operation. This is a good fit for may's internal run-queues,
+/// where the queued payload (a task or coroutine handle) already owns
+/// stable storage.
+///
+/// As with the boxed `Queue`, this is not cloneable, but it may be safely
+/// shared so long as there is only one popper at a time (many pushers are
+/// allowed).
+pub struct Queue<T: Node> {
+    head: AtomicPtr<T>,
+    tail: UnsafeCell<*mut T>,
+    // A sentinel node owned by the queue itself (never handed back to a
+    // caller) that `pop` re-threads onto the list whenever the consumer
+    // would otherwise have to return the only node the producers have
+    // linked so far. Real data never gets stuck behind it.
+    stub: *mut T,
+}
+
+unsafe impl<T: Node + Send> Send for Queue<T> {}
+unsafe impl<T: Node + Send> Sync for Queue<T> {}
+
+impl<T: Node> Queue<T> {
+    /// Creates a new empty queue, using `stub` as the queue's internal
+    /// sentinel node.
+    ///
+    /// # Safety
+    ///
+    /// `stub` must be a valid, uniquely-owned pointer from `Box::into_raw`
+    /// that the caller is transferring to the queue for as long as the queue
+    /// lives; `stub` is never popped or returned to a caller, and dropping
+    /// the queue frees it with `Box::from_raw`.
+    pub unsafe fn new(stub: *mut T) -> Queue<T> {
+        (*stub).next().store(ptr::null_mut(), Ordering::Relaxed);
+        Queue {
+            head: AtomicPtr::new(stub),
+            tail: UnsafeCell::new(stub),
+            stub,
+        }
+    }
+
+    /// Pushes `n` onto this queue.
+    ///
+    /// # Safety
+    ///
+    /// `n` must be a valid, uniquely-owned pointer not currently linked
+    /// into any other queue. Ownership of the pointee passes to the queue
+    /// until a matching `pop` hands it back.
+    pub unsafe fn push(&self, n: *mut T) {
+        (*n).next().store(ptr::null_mut(), Ordering::Relaxed);
+        let prev = self.head.swap(n, Ordering::AcqRel);
+        (*prev).next().store(n, Ordering::Release);
+    }
+
+    /// Pops an element from this queue, handing ownership back to the
+    /// caller.
+    ///
+    /// Returns `None` both when the queue is genuinely empty and when a
+    /// concurrent `push` has linked onto `head` but not yet finished
+    /// storing its predecessor's `next` pointer; the caller is expected to
+    /// retry rather than spin here, since this queue has no opinion on
+    /// what a given `T` should do while it waits.
+    pub fn pop(&self) -> Option<*mut T> {
+        unsafe {
+            let mut tail = *self.tail.get();
+            let mut next = (*tail).next().load(Ordering::Acquire);
+
+            if tail == self.stub {
+                if next.is_null() {
+                    return None;
+                }
+                *self.tail.get() = next;
+                tail = next;
+                next = (*next).next().load(Ordering::Acquire);
+            }
+
+            if !next.is_null() {
+                *self.tail.get() = next;
+                return Some(tail);
+            }
+
+            if self.head.load(Ordering::Acquire) != tail {
+                return None;
+            }
+
+            // `tail` is the only node producers have linked so far and we
+            // are about to hand it back, so give the list somewhere else to
+            // land by re-threading the stub behind it, then check once more
+            // for data that raced in while we were looking.
+            self.push(self.stub);
+            next = (*tail).next().load(Ordering::Acquire);
+            if !next.is_null() {
+                *self.tail.get() = next;
+                return Some(tail);
+            }
+            None
+        }
+    }
+}
+
+impl<T: Node> Drop for Queue<T> {
+    fn drop(&mut self) {
+        // Walk whatever is still linked from `tail` onward and reclaim each
+        // real node via `Node::reclaim`, so a queue dropped with pending
+        // pushes (e.g. a scheduler shutting down with queued run-queue
+        // tasks) doesn't leak them or skip their `Drop` impls.
+        //
+        // `self.stub` is skipped here rather than relied upon to turn up in
+        // the walk: `pop` only re-links it transiently (when the queue
+        // drains to exactly one node), so in the common case of a queue
+        // still holding items at drop time, the stub was passed over long
+        // ago and isn't reachable from the current `tail` at all. It is
+        // always queue-owned and always a `Box`, so it's freed
+        // unconditionally, exactly once, below.
+        unsafe {
+            let mut cur = *self.tail.get();
+            while !cur.is_null() {
+                let next = (*cur).next().load(Ordering::Relaxed);
+                if cur != self.stub {
+                    T::reclaim(cur);
+                }
+                cur = next;
+            }
+            let _: Box<T> = Box::from_raw(self.stub);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::AtomicUsize;
+
+    struct Counted {
+        next: AtomicPtr<Counted>,
+        drops: *const AtomicUsize,
+    }
+
+    impl Counted {
+        fn new(drops: &AtomicUsize) -> *mut Counted {
+            Box::into_raw(Box::new(Counted {
+                next: AtomicPtr::new(ptr::null_mut()),
+                drops: drops as *const AtomicUsize,
+            }))
+        }
+    }
+
+    impl Node for Counted {
+        fn next(&self) -> &AtomicPtr<Self> {
+            &self.next
+        }
+    }
+
+    impl Drop for Counted {
+        fn drop(&mut self) {
+            unsafe {
+                (*self.drops).fetch_add(1, Ordering::SeqCst);
+            }
+        }
+    }
+
+    #[test]
+    fn push_pop_fifo() {
+        let drops = AtomicUsize::new(0);
+        unsafe {
+            let q = Queue::new(Counted::new(&drops));
+            let a = Counted::new(&drops);
+            let b = Counted::new(&drops);
+            q.push(a);
+            q.push(b);
+            assert_eq!(q.pop(), Some(a));
+            assert_eq!(q.pop(), Some(b));
+            assert_eq!(q.pop(), None);
+            drop(Box::from_raw(a));
+            drop(Box::from_raw(b));
+            // `q` still owns the stub; dropping it here accounts for one
+            // more.
+            drop(q);
+        }
+        assert_eq!(drops.load(Ordering::SeqCst), 3);
+    }
+
+    #[test]
+    fn drop_reclaims_stub_and_pending_nodes() {
+        let drops = AtomicUsize::new(0);
+        unsafe {
+            let q = Queue::new(Counted::new(&drops));
+            for _ in 0..7 {
+                q.push(Counted::new(&drops));
+            }
+            // Drain a couple so the stub is no longer anywhere near `tail`
+            // by the time the queue itself is dropped.
+            for _ in 0..2 {
+                let n = q.pop().unwrap();
+                drop(Box::from_raw(n));
+            }
+            drop(q);
+        }
+        // stub + 7 pushed nodes, every one of them dropped exactly once.
+        assert_eq!(drops.load(Ordering::SeqCst), 8);
+    }
+}